@@ -0,0 +1,16 @@
+use thiserror::Error;
+
+/// Why a single `CRON_JOBS` entry failed to parse. Carried alongside the
+/// 1-based entry number so `main` can report exactly which line was bad
+/// instead of silently shrinking the job list.
+#[derive(Debug, Error)]
+pub enum JobParseError {
+    #[error("expected at least 3 pipe-delimited fields (method|url|schedule), found {0}")]
+    MissingFields(usize),
+    #[error("unknown HTTP method: {0}")]
+    UnknownMethod(String),
+    #[error("url must not be empty")]
+    EmptyUrl,
+    #[error("invalid cron schedule: {0}")]
+    BadSchedule(String),
+}
@@ -1,15 +1,50 @@
-use std::{env, process::exit, thread, time::Duration};
+mod error;
+mod notify;
+mod pool;
+mod store;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{env, process::exit, thread, time::{Duration, Instant}};
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
 use std::str::FromStr;
 
+use error::JobParseError;
+use notify::{JobEvent, JobOutcome, NotifyFlags, Notifiers};
+use pool::ThreadPool;
+use store::RunStore;
+
+/// Index into the parsed job list; used as the tie-breaker key in the
+/// scheduling heap and to look up a job's busy flag.
+type JobId = usize;
+
+#[derive(Debug)]
 struct Job {
     method: String,
     url: String,
     schedule: Schedule,
-    next_fire: DateTime<Utc>,
+    tz: Tz,
     headers: Vec<(String, String)>,
     body: Option<String>,
+    notify: NotifyFlags,
+    max_retries: Option<u32>,
+}
+
+/// Computes the next UTC fire time for `schedule`, evaluated in `tz` so that
+/// cron expressions like "0 9 * * *" mean 09:00 local time (DST-aware)
+/// rather than 09:00 UTC.
+fn next_fire_in(schedule: &Schedule, tz: Tz, after: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    let mut upcoming = schedule.upcoming(tz);
+    let next_local = match after {
+        Some(after) => upcoming
+            .find(|dt| dt.with_timezone(&Utc) > after)?,
+        None => upcoming.next()?,
+    };
+    Some(next_local.with_timezone(&Utc))
 }
 
 fn env_or_exit(key: &str) -> String {
@@ -40,43 +75,163 @@ fn parse_headers(s: &str) -> Vec<(String, String)> {
 }
 
 fn split_jobs(spec: &str) -> impl Iterator<Item = &str> {
-    spec.split(|c| c == ';' || c == '\n' || c == '\r')
+    spec.split([';', '\n', '\r'])
         .map(|s| s.trim())
         .filter(|s| !s.is_empty())
 }
 
-fn parse_jobs(spec: &str) -> Vec<Job> {
-    let mut v = Vec::new();
-    for j in split_jobs(spec) {
-        let parts: Vec<&str> = j.splitn(5, '|').collect();
-        if parts.len() < 3 {
-            continue;
-        }
+fn parse_job_line(j: &str) -> Result<Job, JobParseError> {
+    // `body` is free-form and the field most likely to contain a literal
+    // `|` (e.g. JSON), so it's parsed last via `splitn`'s remainder rather
+    // than sitting between fixed-token fields where a stray `|` would
+    // shift everything after it.
+    let parts: Vec<&str> = j.splitn(8, '|').collect();
+    if parts.len() < 3 {
+        return Err(JobParseError::MissingFields(parts.len()));
+    }
 
-        let method = parts[0].trim().to_uppercase();
-        let allowed = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
-        if !allowed.contains(&method.as_str()) {
-            continue;
-        }
+    let method = parts[0].trim().to_uppercase();
+    let allowed = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+    if !allowed.contains(&method.as_str()) {
+        return Err(JobParseError::UnknownMethod(method));
+    }
 
-        let url = parts[1].trim().to_string();
-        let schedule = match Schedule::from_str(parts[2].trim()) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
-        let headers = if parts.len() >= 4 { parse_headers(parts[3]) } else { Vec::new() };
-        let body = if parts.len() == 5 {
-            let b = parts[4].to_string();
-            if b.is_empty() { None } else { Some(b) }
-        } else {
-            None
-        };
+    let url = parts[1].trim().to_string();
+    if url.is_empty() {
+        return Err(JobParseError::EmptyUrl);
+    }
+    let schedule = Schedule::from_str(parts[2].trim())
+        .map_err(|e| JobParseError::BadSchedule(e.to_string()))?;
 
-        if let Some(next) = schedule.upcoming(Utc).next() {
-            v.push(Job { method, url, schedule, next_fire: next, headers, body });
+    let headers = if parts.len() >= 4 { parse_headers(parts[3]) } else { Vec::new() };
+    let tz = if parts.len() >= 5 {
+        parts[4].trim().parse::<Tz>().unwrap_or(Tz::UTC)
+    } else {
+        Tz::UTC
+    };
+    let notify = if parts.len() >= 6 {
+        NotifyFlags::parse(parts[5])
+    } else {
+        NotifyFlags::default()
+    };
+    // Per-job override for `CRON_MAX_RETRIES`; an empty or unparseable
+    // field falls back to the global default rather than erroring, since
+    // most jobs don't need to tune this.
+    let max_retries = if parts.len() >= 7 {
+        let v = parts[6].trim();
+        if v.is_empty() { None } else { v.parse().ok() }
+    } else {
+        None
+    };
+    let body = if parts.len() == 8 {
+        let b = parts[7].to_string();
+        if b.is_empty() { None } else { Some(b) }
+    } else {
+        None
+    };
+
+    if next_fire_in(&schedule, tz, None).is_none() {
+        return Err(JobParseError::BadSchedule("schedule has no upcoming occurrences".to_string()));
+    }
+
+    Ok(Job { method, url, schedule, tz, headers, body, notify, max_retries })
+}
+
+/// Parses every pipe-delimited entry in `CRON_JOBS`, returning one
+/// `Result` per entry (1-based position alongside any error) instead of
+/// silently dropping malformed lines. Callers decide whether to skip or
+/// abort on a parse error.
+fn parse_jobs(spec: &str) -> Vec<Result<Job, (usize, JobParseError)>> {
+    split_jobs(spec)
+        .enumerate()
+        .map(|(idx, j)| parse_job_line(j).map_err(|e| (idx + 1, e)))
+        .collect()
+}
+
+/// Performs a single HTTP attempt for `method` against `url`. Building a
+/// fresh `ureq` request per call keeps this cheap to retry: `RequestBuilder`
+/// is consumed on send, so callers loop by calling `dispatch` again rather
+/// than reusing a builder.
+fn dispatch(
+    method: &str,
+    url: &str,
+    secret: &str,
+    headers: &[(String, String)],
+    body: &Option<String>,
+) -> Result<ureq::http::Response<ureq::Body>, ureq::Error> {
+    match method {
+        "GET" => {
+            let req = apply_headers(ureq::get(url), secret, headers);
+            match body {
+                Some(b) => req.force_send_body().send(b.as_bytes()),
+                None => req.call(),
+            }
+        }
+        "HEAD" => {
+            let req = apply_headers(ureq::head(url), secret, headers);
+            match body {
+                Some(b) => req.force_send_body().send(b.as_bytes()),
+                None => req.call(),
+            }
+        }
+        "OPTIONS" => {
+            let req = apply_headers(ureq::options(url), secret, headers);
+            match body {
+                Some(b) => req.force_send_body().send(b.as_bytes()),
+                None => req.call(),
+            }
+        }
+        "DELETE" => {
+            let req = apply_headers(ureq::delete(url), secret, headers);
+            match body {
+                Some(b) => req.force_send_body().send(b.as_bytes()),
+                None => req.call(),
+            }
+        }
+        "POST" => {
+            let req = apply_headers(ureq::post(url), secret, headers);
+            match body {
+                Some(b) => req.send(b.as_bytes()),
+                None => req.send_empty(),
+            }
+        }
+        "PUT" => {
+            let req = apply_headers(ureq::put(url), secret, headers);
+            match body {
+                Some(b) => req.send(b.as_bytes()),
+                None => req.send_empty(),
+            }
         }
+        "PATCH" => {
+            let req = apply_headers(ureq::patch(url), secret, headers);
+            match body {
+                Some(b) => req.send(b.as_bytes()),
+                None => req.send_empty(),
+            }
+        }
+        _ => unreachable!(),
     }
-    v
+}
+
+/// Whether a failed attempt is worth retrying: transport-level errors (DNS,
+/// connect, timeout, ...) and the handful of HTTP statuses that usually
+/// indicate a transient upstream problem. 4xx other than 429 means the
+/// request itself is wrong, so retrying would just repeat the failure.
+fn is_retryable(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::StatusCode(code) => matches!(code, 429 | 502 | 503 | 504),
+        _ => true,
+    }
+}
+
+/// Exponential backoff starting at 1s, doubling per attempt, capped at 30s,
+/// with a little random jitter so many jobs retrying at once don't all
+/// re-hit the same endpoint in lockstep.
+fn backoff_delay(attempt: u32) -> Duration {
+    let base_ms = 1_000u64.saturating_mul(1u64 << attempt.min(5));
+    let capped_ms = base_ms.min(30_000);
+    let jitter_ms = rand::random::<u64>() % 250;
+    Duration::from_millis(capped_ms + jitter_ms)
 }
 
 fn apply_headers<B>(
@@ -101,102 +256,283 @@ fn apply_headers<B>(
     req
 }
 
+/// Opens the optional `STATE_DB` sqlite store, exiting if the path is set
+/// but can't be opened.
+fn open_state_db() -> Option<RunStore> {
+    let path = env::var("STATE_DB").ok()?;
+    match RunStore::open(&path) {
+        Ok(store) => Some(store),
+        Err(e) => {
+            eprintln!("Failed to open STATE_DB {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
+
+/// `--history` subcommand: prints recent runs and per-job totals from
+/// `STATE_DB` and exits. Requires `STATE_DB` since there's nothing to query
+/// otherwise.
+fn run_history_cli() {
+    let path = env_or_exit("STATE_DB");
+    let store = match RunStore::open(&path) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to open STATE_DB {}: {}", path, e);
+            exit(1);
+        }
+    };
+    if let Err(e) = store.print_history(20) {
+        eprintln!("Failed to read run history: {}", e);
+        exit(1);
+    }
+}
+
 fn main() {
+    if env::args().nth(1).as_deref() == Some("--history") {
+        run_history_cli();
+        return;
+    }
+
     let secret = env_or_exit("SECRET");
     let jobs_spec = env_or_exit("CRON_JOBS");
-    let mut jobs = parse_jobs(&jobs_spec);
+    let strict = env::var("CRON_STRICT").map(|v| v == "1").unwrap_or(false);
+
+    let mut jobs = Vec::new();
+    for result in parse_jobs(&jobs_spec) {
+        match result {
+            Ok(job) => jobs.push(job),
+            Err((line, e)) => {
+                eprintln!("CRON_JOBS entry {}: {}", line, e);
+                if strict {
+                    exit(1);
+                }
+            }
+        }
+    }
     if jobs.is_empty() {
         eprintln!("No valid jobs parsed from CRON_JOBS");
         exit(1);
     }
 
-    let jitter_ms = 500i64;
+    // Default retry budget for jobs that don't set their own `max_retries`
+    // field.
+    let default_max_retries: u32 = env::var("CRON_MAX_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let workers: usize = env::var("CRON_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(4);
+
+    let secret = Arc::new(secret);
+    let jobs = Arc::new(jobs);
+    let busy: Arc<Vec<AtomicBool>> = Arc::new((0..jobs.len()).map(|_| AtomicBool::new(false)).collect());
+    let notifiers = Arc::new(Notifiers::from_env());
+    let state_db = Arc::new(Mutex::new(open_state_db()));
+    let pool = ThreadPool::new(workers.max(1));
+
+    let mut heap: BinaryHeap<Reverse<(DateTime<Utc>, JobId)>> = BinaryHeap::new();
+    for (id, job) in jobs.iter().enumerate() {
+        if let Some(next) = next_fire_in(&job.schedule, job.tz, None) {
+            heap.push(Reverse((next, id)));
+        }
+    }
+    if heap.is_empty() {
+        eprintln!("Internal error: empty schedule set");
+        exit(1);
+    }
 
     loop {
-        let now = Utc::now();
-        let earliest = match jobs.iter().map(|j| j.next_fire).min() {
-            Some(dt) => dt,
+        let Reverse((scheduled_at, job_id)) = match heap.pop() {
+            Some(entry) => entry,
             None => {
-                eprintln!("Internal error: empty schedule set");
+                eprintln!("All scheduled job occurrences exhausted (e.g. a year-bound cron expression ran out); nothing left to run");
                 exit(1);
             }
         };
-        let sleep_ns = (earliest - now).num_nanoseconds().unwrap_or(0);
+
+        let sleep_ns = (scheduled_at - Utc::now()).num_nanoseconds().unwrap_or(0);
         if sleep_ns > 0 {
             thread::sleep(Duration::from_nanos(sleep_ns as u64));
         }
 
-        let fired_at = Utc::now();
-        for j in jobs.iter_mut() {
-            if (fired_at - j.next_fire).num_milliseconds().abs() <= jitter_ms {
-                let ts = Utc::now().to_rfc3339();
+        if let Some(next) = next_fire_in(&jobs[job_id].schedule, jobs[job_id].tz, Some(scheduled_at)) {
+            heap.push(Reverse((next, job_id)));
+        }
 
-                let result = match j.method.as_str() {
-                    "GET" => {
-                        let req = apply_headers(ureq::get(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.force_send_body().send(b.as_bytes()),
-                            None => req.call(),
-                        }
-                    }
-                    "HEAD" => {
-                        let req = apply_headers(ureq::head(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.force_send_body().send(b.as_bytes()),
-                            None => req.call(),
-                        }
-                    }
-                    "OPTIONS" => {
-                        let req = apply_headers(ureq::options(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.force_send_body().send(b.as_bytes()),
-                            None => req.call(),
-                        }
-                    }
-                    "DELETE" => {
-                        let req = apply_headers(ureq::delete(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.force_send_body().send(b.as_bytes()),
-                            None => req.call(),
-                        }
-                    }
-                    "POST" => {
-                        let req = apply_headers(ureq::post(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.send(b.as_bytes()),
-                            None => req.send_empty(),
-                        }
-                    }
-                    "PUT" => {
-                        let req = apply_headers(ureq::put(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.send(b.as_bytes()),
-                            None => req.send_empty(),
-                        }
-                    }
-                    "PATCH" => {
-                        let req = apply_headers(ureq::patch(&j.url), &secret, &j.headers);
-                        match &j.body {
-                            Some(b) => req.send(b.as_bytes()),
-                            None => req.send_empty(),
-                        }
+        if busy[job_id].swap(true, Ordering::SeqCst) {
+            eprintln!(
+                "{} | SKIP busy | {} {}",
+                Utc::now().to_rfc3339(),
+                jobs[job_id].method,
+                jobs[job_id].url
+            );
+            continue;
+        }
+
+        let jobs = Arc::clone(&jobs);
+        let busy = Arc::clone(&busy);
+        let secret = Arc::clone(&secret);
+        let notifiers = Arc::clone(&notifiers);
+        let state_db = Arc::clone(&state_db);
+
+        pool.execute(move || {
+            let job = &jobs[job_id];
+            let ts = Utc::now().to_rfc3339();
+            let attempt_start = Instant::now();
+            let max_retries = job.max_retries.unwrap_or(default_max_retries);
+
+            let mut attempt = 0u32;
+            let result = loop {
+                let attempt_result = dispatch(&job.method, &job.url, &secret, &job.headers, &job.body);
+                match &attempt_result {
+                    Err(e) if attempt < max_retries && is_retryable(e) => {
+                        let delay = backoff_delay(attempt);
+                        attempt += 1;
+                        eprintln!(
+                            "{} | RETRY {}/{} | {} {} | {}",
+                            Utc::now().to_rfc3339(),
+                            attempt,
+                            max_retries,
+                            job.method,
+                            job.url,
+                            e
+                        );
+                        thread::sleep(delay);
                     }
-                    _ => unreachable!(),
-                };
+                    _ => break attempt_result,
+                }
+            };
 
-                match result {
-                    Ok(resp) => println!("{} | OK | {} {} | {}", ts, j.method, j.url, resp.status()),
-                    Err(ureq::Error::StatusCode(code)) => {
-                        let cat = if (400..500).contains(&(code as i32)) { "client error" } else { "server error" };
-                        eprintln!("{} | FAIL | {} {} | HTTP {} ({})", ts, j.method, j.url, code, cat);
+            let outcome = match &result {
+                Ok(resp) => JobOutcome::Success { status: resp.status().as_u16() },
+                Err(ureq::Error::StatusCode(code)) => {
+                    if (400..500).contains(&(*code as i32)) {
+                        JobOutcome::ClientError { status: *code }
+                    } else {
+                        JobOutcome::ServerError { status: *code }
                     }
-                    Err(e) => eprintln!("{} | FAIL | {} {} | transport error: {}", ts, j.method, j.url, e),
                 }
+                Err(e) => JobOutcome::TransportError { detail: e.to_string() },
+            };
 
-                if let Some(n) = j.schedule.upcoming(Utc).filter(|dt| *dt > j.next_fire).next() {
-                    j.next_fire = n;
+            match &result {
+                Ok(resp) => println!("{} | OK | {} {} | {}", ts, job.method, job.url, resp.status()),
+                Err(ureq::Error::StatusCode(code)) => {
+                    let cat = if (400..500).contains(&(*code as i32)) { "client error" } else { "server error" };
+                    eprintln!("{} | FAIL | {} {} | HTTP {} ({})", ts, job.method, job.url, code, cat);
                 }
+                Err(e) => eprintln!("{} | FAIL | {} {} | transport error: {}", ts, job.method, job.url, e),
             }
-        }
+
+            if let Some(store) = state_db.lock().expect("state_db mutex poisoned").as_ref() {
+                let outcome_str = match &outcome {
+                    JobOutcome::Success { status } => format!("OK {}", status),
+                    JobOutcome::ClientError { status } => format!("HTTP {} (client error)", status),
+                    JobOutcome::ServerError { status } => format!("HTTP {} (server error)", status),
+                    JobOutcome::TransportError { detail } => format!("transport error: {}", detail),
+                };
+                let latency_ms = attempt_start.elapsed().as_millis() as i64;
+                if let Err(e) = store.record_run(&job.method, &job.url, scheduled_at, Utc::now(), &outcome_str, latency_ms) {
+                    eprintln!("{} | STATE_DB FAIL | {}", ts, e);
+                }
+            }
+
+            let event = JobEvent { timestamp: Utc::now(), method: job.method.clone(), url: job.url.clone(), outcome };
+            notifiers.notify_job(&event, job.notify);
+
+            busy[job_id].store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_job_line_rejects_too_few_fields() {
+        let err = parse_job_line("GET|https://example.com").unwrap_err();
+        assert!(matches!(err, JobParseError::MissingFields(2)));
+    }
+
+    #[test]
+    fn parse_job_line_rejects_unknown_method() {
+        let err = parse_job_line("TRACE|https://example.com|* * * * * *").unwrap_err();
+        assert!(matches!(err, JobParseError::UnknownMethod(m) if m == "TRACE"));
+    }
+
+    #[test]
+    fn parse_job_line_rejects_empty_url() {
+        let err = parse_job_line("GET| |* * * * * *").unwrap_err();
+        assert!(matches!(err, JobParseError::EmptyUrl));
+    }
+
+    #[test]
+    fn parse_job_line_rejects_bad_schedule() {
+        let err = parse_job_line("GET|https://example.com|not a schedule").unwrap_err();
+        assert!(matches!(err, JobParseError::BadSchedule(_)));
+    }
+
+    #[test]
+    fn parse_job_line_accepts_minimal_job() {
+        let job = parse_job_line("get|https://example.com|* * * * * *").unwrap();
+        assert_eq!(job.method, "GET");
+        assert_eq!(job.url, "https://example.com");
+        assert_eq!(job.tz, Tz::UTC);
+    }
+
+    #[test]
+    fn parse_job_line_parses_tz_and_notify_fields() {
+        let job = parse_job_line(
+            "GET|https://example.com|* * * * * *|X-Foo:bar|Europe/Paris|webhook,snitch|3|body text",
+        )
+        .unwrap();
+        assert_eq!(job.tz, Tz::Europe__Paris);
+        assert_eq!(job.headers, vec![("X-Foo".to_string(), "bar".to_string())]);
+        assert_eq!(job.body.as_deref(), Some("body text"));
+        assert!(job.notify.webhook);
+        assert!(job.notify.snitch);
+        assert_eq!(job.max_retries, Some(3));
+    }
+
+    #[test]
+    fn parse_job_line_defaults_max_retries_to_none() {
+        let job = parse_job_line("GET|https://example.com|* * * * * *").unwrap();
+        assert_eq!(job.max_retries, None);
+    }
+
+    #[test]
+    fn parse_job_line_keeps_body_pipes_intact_when_trailing() {
+        let job = parse_job_line(
+            r#"POST|https://example.com|* * * * * *|||||{"a":1,"b":"x|y"}"#,
+        )
+        .unwrap();
+        assert_eq!(job.body.as_deref(), Some(r#"{"a":1,"b":"x|y"}"#));
+    }
+
+    #[test]
+    fn is_retryable_covers_transient_statuses_only() {
+        assert!(is_retryable(&ureq::Error::StatusCode(429)));
+        assert!(is_retryable(&ureq::Error::StatusCode(502)));
+        assert!(is_retryable(&ureq::Error::StatusCode(503)));
+        assert!(is_retryable(&ureq::Error::StatusCode(504)));
+        assert!(!is_retryable(&ureq::Error::StatusCode(400)));
+        assert!(!is_retryable(&ureq::Error::StatusCode(404)));
+        assert!(is_retryable(&ureq::Error::HostNotFound));
+    }
+
+    #[test]
+    fn backoff_delay_doubles_from_a_one_second_base() {
+        assert!((1_000..1_250).contains(&backoff_delay(0).as_millis()));
+        assert!((2_000..2_250).contains(&backoff_delay(1).as_millis()));
+        assert!((4_000..4_250).contains(&backoff_delay(2).as_millis()));
+    }
+
+    #[test]
+    fn backoff_delay_caps_at_thirty_seconds() {
+        assert!((30_000..30_250).contains(&backoff_delay(5).as_millis()));
+        assert!((30_000..30_250).contains(&backoff_delay(20).as_millis()));
     }
 }
@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+
+/// Outcome of a single job fire, in the shape notifiers care about. Mirrors
+/// the `client error`/`server error`/`transport error` categorization the
+/// runner already logs to stderr.
+pub enum JobOutcome {
+    Success { status: u16 },
+    ClientError { status: u16 },
+    ServerError { status: u16 },
+    TransportError { detail: String },
+}
+
+pub struct JobEvent {
+    pub timestamp: DateTime<Utc>,
+    pub method: String,
+    pub url: String,
+    pub outcome: JobOutcome,
+}
+
+/// Per-job opt-in into the configured notifier sinks, parsed from the
+/// optional `notify` pipe field (e.g. `webhook`, `snitch`, `webhook,snitch`).
+/// A job that doesn't name a sink never notifies it, even if the sink is
+/// configured globally via its `NOTIFY_*_URL` env var — this is what lets a
+/// noisy low-priority job stay silent while a critical job pages.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NotifyFlags {
+    pub webhook: bool,
+    pub snitch: bool,
+}
+
+impl NotifyFlags {
+    pub fn parse(s: &str) -> Self {
+        let mut flags = Self::default();
+        for token in s.split(',').map(|t| t.trim()) {
+            match token.to_ascii_lowercase().as_str() {
+                "webhook" => flags.webhook = true,
+                "snitch" => flags.snitch = true,
+                _ => {}
+            }
+        }
+        flags
+    }
+}
+
+/// A sink that reacts to job outcomes. Implementations decide for themselves
+/// which outcomes they care about (a webhook only alerts on failure, a
+/// dead-man's-snitch only pings on success) — `notify` is called for every
+/// fire and is expected to no-op on outcomes it doesn't handle.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, event: &JobEvent);
+}
+
+/// Posts a JSON payload to a Slack/Discord-style incoming webhook whenever a
+/// job fails. Successes are ignored — this sink exists to page someone.
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, event: &JobEvent) {
+        let (category, detail) = match &event.outcome {
+            JobOutcome::Success { .. } => return,
+            JobOutcome::ClientError { status } => ("client error", format!("HTTP {}", status)),
+            JobOutcome::ServerError { status } => ("server error", format!("HTTP {}", status)),
+            JobOutcome::TransportError { detail } => ("transport error", detail.clone()),
+        };
+
+        let text = format!(
+            "cron-rust job failed\ntime: {}\nrequest: {} {}\ncategory: {}\ndetail: {}",
+            event.timestamp.to_rfc3339(),
+            event.method,
+            event.url,
+            category,
+            detail
+        );
+        let payload = format!(r#"{{"text":{}}}"#, json_escape(&text));
+
+        if let Err(e) = ureq::post(&self.url).send(payload.as_bytes()) {
+            eprintln!(
+                "{} | NOTIFY FAIL | webhook {} | {}",
+                event.timestamp.to_rfc3339(),
+                self.url,
+                e
+            );
+        }
+    }
+}
+
+/// Pings a dead man's snitch (or any heartbeat-style URL) on every
+/// successful fire. Missing pings are what the snitch service alerts on, so
+/// failures are intentionally not reported here.
+pub struct SnitchNotifier {
+    url: String,
+}
+
+impl SnitchNotifier {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl Notifier for SnitchNotifier {
+    fn notify(&self, event: &JobEvent) {
+        if !matches!(event.outcome, JobOutcome::Success { .. }) {
+            return;
+        }
+        if let Err(e) = ureq::get(&self.url).call() {
+            eprintln!(
+                "{} | NOTIFY FAIL | snitch {} | {}",
+                event.timestamp.to_rfc3339(),
+                self.url,
+                e
+            );
+        }
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The process-wide notifier configuration: at most one webhook sink and one
+/// snitch sink, built once from `NOTIFY_WEBHOOK_URL` / `NOTIFY_SNITCH_URL`.
+/// Whether a given job actually reaches a configured sink is decided per
+/// fire by that job's `NotifyFlags`, not by this struct.
+pub struct Notifiers {
+    webhook: Option<WebhookNotifier>,
+    snitch: Option<SnitchNotifier>,
+}
+
+impl Notifiers {
+    /// Builds the notifier set from environment configuration.
+    /// `NOTIFY_WEBHOOK_URL` configures the failure-alert sink and
+    /// `NOTIFY_SNITCH_URL` configures the success-heartbeat sink; either,
+    /// both, or neither may be set. A job only reaches a sink here if it
+    /// also opted in via its `notify` field.
+    pub fn from_env() -> Self {
+        let webhook = std::env::var("NOTIFY_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(WebhookNotifier::new);
+        let snitch = std::env::var("NOTIFY_SNITCH_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(SnitchNotifier::new);
+        Self { webhook, snitch }
+    }
+
+    /// Notifies only the sinks `flags` opts this job into.
+    pub fn notify_job(&self, event: &JobEvent, flags: NotifyFlags) {
+        if flags.webhook {
+            if let Some(webhook) = &self.webhook {
+                webhook.notify(event);
+            }
+        }
+        if flags.snitch {
+            if let Some(snitch) = &self.snitch {
+                snitch.notify(event);
+            }
+        }
+    }
+}
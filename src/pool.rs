@@ -0,0 +1,61 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// A small fixed-size worker pool so job dispatch (blocking HTTP I/O) never
+/// runs on the scheduling thread. `execute` just enqueues; workers pull and
+/// run tasks as they free up.
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Task>>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "thread pool size must be at least 1");
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size).map(|id| Worker::new(id, Arc::clone(&receiver))).collect();
+        Self { workers, sender: Some(sender) }
+    }
+
+    pub fn execute<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.sender
+            .as_ref()
+            .expect("sender dropped before pool")
+            .send(Box::new(task))
+            .expect("worker threads terminated early");
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in &mut self.workers {
+            if let Some(handle) = worker.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+struct Worker {
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(_id: usize, receiver: Arc<Mutex<mpsc::Receiver<Task>>>) -> Self {
+        let handle = thread::spawn(move || loop {
+            let task = receiver.lock().expect("worker queue poisoned").recv();
+            match task {
+                Ok(task) => task(),
+                Err(_) => break,
+            }
+        });
+        Self { handle: Some(handle) }
+    }
+}
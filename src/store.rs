@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+
+/// Durable record of every fire, enabled by setting `STATE_DB` to a sqlite
+/// file path. Kept deliberately simple: one row per fire, opened once in
+/// `main` and written to once per fire, after its final attempt settles.
+pub struct RunStore {
+    conn: Connection,
+}
+
+impl RunStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                method TEXT NOT NULL,
+                url TEXT NOT NULL,
+                scheduled_at TEXT NOT NULL,
+                fired_at TEXT NOT NULL,
+                outcome TEXT NOT NULL,
+                latency_ms INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+
+    pub fn record_run(
+        &self,
+        method: &str,
+        url: &str,
+        scheduled_at: DateTime<Utc>,
+        fired_at: DateTime<Utc>,
+        outcome: &str,
+        latency_ms: i64,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO runs (method, url, scheduled_at, fired_at, outcome, latency_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                method,
+                url,
+                scheduled_at.to_rfc3339(),
+                fired_at.to_rfc3339(),
+                outcome,
+                latency_ms
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Prints the most recent `limit` runs followed by a per-job success /
+    /// failure breakdown, for the `--history` CLI.
+    pub fn print_history(&self, limit: u32) -> rusqlite::Result<()> {
+        let mut recent_stmt = self.conn.prepare(
+            "SELECT fired_at, method, url, outcome, latency_ms
+             FROM runs ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = recent_stmt.query_map(params![limit], |row| {
+            let fired_at: String = row.get(0)?;
+            let method: String = row.get(1)?;
+            let url: String = row.get(2)?;
+            let outcome: String = row.get(3)?;
+            let latency_ms: i64 = row.get(4)?;
+            Ok((fired_at, method, url, outcome, latency_ms))
+        })?;
+
+        println!("recent runs:");
+        for row in rows {
+            let (fired_at, method, url, outcome, latency_ms) = row?;
+            println!("  {} | {} {} | {} | {}ms", fired_at, method, url, outcome, latency_ms);
+        }
+
+        let mut summary_stmt = self.conn.prepare(
+            "SELECT method, url,
+                    SUM(CASE WHEN outcome LIKE 'OK%' THEN 1 ELSE 0 END) AS ok_count,
+                    SUM(CASE WHEN outcome LIKE 'OK%' THEN 0 ELSE 1 END) AS fail_count
+             FROM runs GROUP BY method, url ORDER BY url",
+        )?;
+        let summary_rows = summary_stmt.query_map([], |row| {
+            let method: String = row.get(0)?;
+            let url: String = row.get(1)?;
+            let ok_count: i64 = row.get(2)?;
+            let fail_count: i64 = row.get(3)?;
+            Ok((method, url, ok_count, fail_count))
+        })?;
+
+        println!("per-job totals:");
+        for row in summary_rows {
+            let (method, url, ok_count, fail_count) = row?;
+            println!("  {} {} | {} ok / {} failed", method, url, ok_count, fail_count);
+        }
+
+        Ok(())
+    }
+}